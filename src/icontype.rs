@@ -1,6 +1,9 @@
 use std;
 use std::fmt;
 
+pub mod palette;
+pub mod toc;
+
 /// Types of icon elements that can be decoded as images or masks.
 #[allow(non_camel_case_types)]
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -13,10 +16,40 @@ pub enum IconType {
     RGB24_32x32,
     /// 32x32 8-bit alpha mask.
     Mask8_32x32,
+    /// 32x32 1-bit icon with 1-bit mask (`ICN#`).
+    Mono1_32x32,
+    /// 16x16 1-bit icon with 1-bit mask (`ics#`).
+    Mono1_16x16,
+    /// 32x32 4-bit indexed icon (`icl4`).
+    Indexed4_32x32,
+    /// 16x16 4-bit indexed icon (`ics4`).
+    Indexed4_16x16,
+    /// 32x32 8-bit indexed icon (`icl8`).
+    Indexed8_32x32,
+    /// 16x16 8-bit indexed icon (`ics8`).
+    Indexed8_16x16,
+    /// 48x48 24-bit icon (without alpha).
+    RGB24_48x48,
+    /// 48x48 8-bit alpha mask.
+    Mask8_48x48,
     /// 128x128 24-bit icon (without alpha).
     RGB24_128x128,
     /// 128x128 8-bit alpha mask.
     Mask8_128x128,
+    /// 16x16 32-bit icon.
+    RGBA32_16x16,
+    /// 16x16 32-bit icon at 2x "retina" density (so, 32 by 32 pixels).
+    RGBA32_16x16_2x,
+    /// 32x32 32-bit icon.
+    RGBA32_32x32,
+    /// 32x32 32-bit icon at 2x "retina" density (so, 64 by 64 pixels).
+    RGBA32_32x32_2x,
+    /// 64x64 32-bit icon.
+    RGBA32_64x64,
+    /// 128x128 32-bit icon.
+    RGBA32_128x128,
+    /// 128x128 32-bit icon at 2x "retina" density (so, 256 by 256 pixels).
+    RGBA32_128x128_2x,
     /// 256x256 32-bit icon.
     RGBA32_256x256,
     /// 256x256 32-bit icon at 2x "retina" density (so, 512 by 512 pixels).
@@ -36,8 +69,23 @@ impl IconType {
             b"s8mk" => Some(IconType::Mask8_16x16),
             b"il32" => Some(IconType::RGB24_32x32),
             b"l8mk" => Some(IconType::Mask8_32x32),
+            b"ICN#" => Some(IconType::Mono1_32x32),
+            b"ics#" => Some(IconType::Mono1_16x16),
+            b"icl4" => Some(IconType::Indexed4_32x32),
+            b"ics4" => Some(IconType::Indexed4_16x16),
+            b"icl8" => Some(IconType::Indexed8_32x32),
+            b"ics8" => Some(IconType::Indexed8_16x16),
+            b"ih32" => Some(IconType::RGB24_48x48),
+            b"h8mk" => Some(IconType::Mask8_48x48),
             b"it32" => Some(IconType::RGB24_128x128),
             b"t8mk" => Some(IconType::Mask8_128x128),
+            b"icp4" => Some(IconType::RGBA32_16x16),
+            b"ic11" => Some(IconType::RGBA32_16x16_2x),
+            b"icp5" => Some(IconType::RGBA32_32x32),
+            b"ic12" => Some(IconType::RGBA32_32x32_2x),
+            b"icp6" => Some(IconType::RGBA32_64x64),
+            b"ic07" => Some(IconType::RGBA32_128x128),
+            b"ic13" => Some(IconType::RGBA32_128x128_2x),
             b"ic08" => Some(IconType::RGBA32_256x256),
             b"ic14" => Some(IconType::RGBA32_256x256_2x),
             b"ic09" => Some(IconType::RGBA32_512x512),
@@ -53,8 +101,23 @@ impl IconType {
             IconType::Mask8_16x16 => OSType(*b"s8mk"),
             IconType::RGB24_32x32 => OSType(*b"il32"),
             IconType::Mask8_32x32 => OSType(*b"l8mk"),
+            IconType::Mono1_32x32 => OSType(*b"ICN#"),
+            IconType::Mono1_16x16 => OSType(*b"ics#"),
+            IconType::Indexed4_32x32 => OSType(*b"icl4"),
+            IconType::Indexed4_16x16 => OSType(*b"ics4"),
+            IconType::Indexed8_32x32 => OSType(*b"icl8"),
+            IconType::Indexed8_16x16 => OSType(*b"ics8"),
+            IconType::RGB24_48x48 => OSType(*b"ih32"),
+            IconType::Mask8_48x48 => OSType(*b"h8mk"),
             IconType::RGB24_128x128 => OSType(*b"it32"),
             IconType::Mask8_128x128 => OSType(*b"t8mk"),
+            IconType::RGBA32_16x16 => OSType(*b"icp4"),
+            IconType::RGBA32_16x16_2x => OSType(*b"ic11"),
+            IconType::RGBA32_32x32 => OSType(*b"icp5"),
+            IconType::RGBA32_32x32_2x => OSType(*b"ic12"),
+            IconType::RGBA32_64x64 => OSType(*b"icp6"),
+            IconType::RGBA32_128x128 => OSType(*b"ic07"),
+            IconType::RGBA32_128x128_2x => OSType(*b"ic13"),
             IconType::RGBA32_256x256 => OSType(*b"ic08"),
             IconType::RGBA32_256x256_2x => OSType(*b"ic14"),
             IconType::RGBA32_512x512 => OSType(*b"ic09"),
@@ -79,8 +142,23 @@ impl IconType {
             IconType::Mask8_16x16 => 16,
             IconType::RGB24_32x32 => 32,
             IconType::Mask8_32x32 => 32,
+            IconType::Mono1_32x32 => 32,
+            IconType::Mono1_16x16 => 16,
+            IconType::Indexed4_32x32 => 32,
+            IconType::Indexed4_16x16 => 16,
+            IconType::Indexed8_32x32 => 32,
+            IconType::Indexed8_16x16 => 16,
+            IconType::RGB24_48x48 => 48,
+            IconType::Mask8_48x48 => 48,
             IconType::RGB24_128x128 => 128,
             IconType::Mask8_128x128 => 128,
+            IconType::RGBA32_16x16 => 16,
+            IconType::RGBA32_16x16_2x => 32,
+            IconType::RGBA32_32x32 => 32,
+            IconType::RGBA32_32x32_2x => 64,
+            IconType::RGBA32_64x64 => 64,
+            IconType::RGBA32_128x128 => 128,
+            IconType::RGBA32_128x128_2x => 256,
             IconType::RGBA32_256x256 => 256,
             IconType::RGBA32_256x256_2x => 512,
             IconType::RGBA32_512x512 => 512,
@@ -105,14 +183,298 @@ impl IconType {
             IconType::Mask8_16x16 => 16,
             IconType::RGB24_32x32 => 32,
             IconType::Mask8_32x32 => 32,
+            IconType::Mono1_32x32 => 32,
+            IconType::Mono1_16x16 => 16,
+            IconType::Indexed4_32x32 => 32,
+            IconType::Indexed4_16x16 => 16,
+            IconType::Indexed8_32x32 => 32,
+            IconType::Indexed8_16x16 => 16,
+            IconType::RGB24_48x48 => 48,
+            IconType::Mask8_48x48 => 48,
             IconType::RGB24_128x128 => 128,
             IconType::Mask8_128x128 => 128,
+            IconType::RGBA32_16x16 => 16,
+            IconType::RGBA32_16x16_2x => 16,
+            IconType::RGBA32_32x32 => 32,
+            IconType::RGBA32_32x32_2x => 32,
+            IconType::RGBA32_64x64 => 64,
+            IconType::RGBA32_128x128 => 128,
+            IconType::RGBA32_128x128_2x => 128,
             IconType::RGBA32_256x256 => 256,
             IconType::RGBA32_256x256_2x => 256,
             IconType::RGBA32_512x512 => 512,
             IconType::RGBA32_512x512_2x => 512,
         }
     }
+
+    /// The icon types that can be used to encode an arbitrary 32-bit RGBA
+    /// source image, ordered from smallest to largest pixel dimensions.
+    const ENCODABLE_RGBA_TYPES: [IconType; 11] = [IconType::RGBA32_16x16,
+                                                   IconType::RGBA32_16x16_2x,
+                                                   IconType::RGBA32_32x32,
+                                                   IconType::RGBA32_32x32_2x,
+                                                   IconType::RGBA32_64x64,
+                                                   IconType::RGBA32_128x128,
+                                                   IconType::RGBA32_128x128_2x,
+                                                   IconType::RGBA32_256x256,
+                                                   IconType::RGBA32_256x256_2x,
+                                                   IconType::RGBA32_512x512,
+                                                   IconType::RGBA32_512x512_2x];
+
+    /// Returns the (square) RGBA icon type whose pixel dimensions exactly
+    /// match `width`x`height`, if any.  Returns `None` for non-square
+    /// dimensions or dimensions with no matching icon type.
+    ///
+    /// Some pixel dimensions (e.g. 32x32) are shared by both a non-retina
+    /// type (`RGBA32_32x32`) and a retina type for a smaller screen size
+    /// (`RGBA32_16x16_2x`); in that case, the non-retina type (the one
+    /// whose `pixel_width()` equals its `screen_width()`) is preferred.
+    ///
+    /// # Examples
+    /// ```
+    /// use icns::IconType;
+    /// assert_eq!(IconType::from_pixel_dimensions(32, 32), Some(IconType::RGBA32_32x32));
+    /// assert_eq!(IconType::from_pixel_dimensions(32, 16), None);
+    /// ```
+    pub fn from_pixel_dimensions(width: u32, height: u32) -> Option<IconType> {
+        if width != height {
+            return None;
+        }
+        let mut candidates: Vec<IconType> = IconType::ENCODABLE_RGBA_TYPES
+            .iter()
+            .cloned()
+            .filter(|icon_type| icon_type.pixel_width() == width)
+            .collect();
+        candidates.sort_by_key(|icon_type| icon_type.screen_width());
+        candidates.into_iter().last()
+    }
+
+    /// Chooses the RGBA icon type best suited for encoding a square source
+    /// image of the given pixel dimensions, preferring the smallest
+    /// supported type that is at least as large as the source image (so the
+    /// image can be scaled down rather than up).  If the source image is
+    /// larger than any supported type, the largest supported type is
+    /// returned instead.
+    ///
+    /// Some pixel dimensions (e.g. 32x32) are shared by both a non-retina
+    /// type (`RGBA32_32x32`) and a retina type for a smaller screen size
+    /// (`RGBA32_16x16_2x`); in that case, `prefer_retina` selects between
+    /// them.
+    ///
+    /// Returns `None` for non-square dimensions.
+    pub fn best_for(width: u32, height: u32, prefer_retina: bool) -> Option<IconType> {
+        if width != height {
+            return None;
+        }
+        let mut candidates: Vec<IconType> = IconType::ENCODABLE_RGBA_TYPES
+            .iter()
+            .cloned()
+            .filter(|icon_type| icon_type.pixel_width() >= width)
+            .collect();
+        let target_width = if candidates.is_empty() {
+            candidates = IconType::ENCODABLE_RGBA_TYPES.to_vec();
+            candidates.iter().map(|t| t.pixel_width()).max().unwrap()
+        } else {
+            candidates.iter().map(|t| t.pixel_width()).min().unwrap()
+        };
+        candidates.retain(|icon_type| icon_type.pixel_width() == target_width);
+        if candidates.len() > 1 {
+            candidates.sort_by_key(|icon_type| icon_type.screen_width());
+            if prefer_retina {
+                candidates.into_iter().next()
+            } else {
+                candidates.into_iter().last()
+            }
+        } else {
+            candidates.into_iter().next()
+        }
+    }
+
+    /// Returns true if this icon type represents an 8-bit alpha mask,
+    /// rather than a color image.
+    pub fn is_mask(self) -> bool {
+        match self {
+            IconType::Mask8_16x16 |
+            IconType::Mask8_32x32 |
+            IconType::Mask8_48x48 |
+            IconType::Mask8_128x128 => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the number of color channels used by this icon type's pixel
+    /// data (e.g. 3 for RGB, 4 for RGBA, or 1 for a mask or indexed/mono
+    /// image).
+    pub fn channels(self) -> u8 {
+        match self.depth() {
+            Depth::One | Depth::Four | Depth::Eight => 1,
+            Depth::TwentyFour => 3,
+            Depth::ThirtyTwo => 4,
+        }
+    }
+
+    /// Returns the number of bits used to store each pixel of this icon
+    /// type's (decoded) pixel data.
+    pub fn bits_per_pixel(self) -> u16 {
+        match self.depth() {
+            Depth::One => 1,
+            Depth::Four => 4,
+            Depth::Eight => 8,
+            Depth::TwentyFour => 24,
+            Depth::ThirtyTwo => 32,
+        }
+    }
+
+    /// Returns the bit depth used by this icon type's pixel data.
+    fn depth(self) -> Depth {
+        match self {
+            IconType::Mono1_32x32 | IconType::Mono1_16x16 => Depth::One,
+            IconType::Indexed4_32x32 | IconType::Indexed4_16x16 => Depth::Four,
+            IconType::Indexed8_32x32 | IconType::Indexed8_16x16 => Depth::Eight,
+            IconType::Mask8_16x16 |
+            IconType::Mask8_32x32 |
+            IconType::Mask8_48x48 |
+            IconType::Mask8_128x128 => Depth::Eight,
+            IconType::RGB24_16x16 |
+            IconType::RGB24_32x32 |
+            IconType::RGB24_48x48 |
+            IconType::RGB24_128x128 => Depth::TwentyFour,
+            IconType::RGBA32_16x16 |
+            IconType::RGBA32_16x16_2x |
+            IconType::RGBA32_32x32 |
+            IconType::RGBA32_32x32_2x |
+            IconType::RGBA32_64x64 |
+            IconType::RGBA32_128x128 |
+            IconType::RGBA32_128x128_2x |
+            IconType::RGBA32_256x256 |
+            IconType::RGBA32_256x256_2x |
+            IconType::RGBA32_512x512 |
+            IconType::RGBA32_512x512_2x => Depth::ThirtyTwo,
+        }
+    }
+
+    /// Returns the on-disk encoding used by this icon type's element data.
+    pub fn encoding(self) -> Encoding {
+        match self {
+            IconType::Mask8_16x16 |
+            IconType::Mask8_32x32 |
+            IconType::Mask8_48x48 |
+            IconType::Mask8_128x128 => Encoding::Mask8,
+            IconType::Mono1_32x32 | IconType::Mono1_16x16 => Encoding::Raw1BitWithMask,
+            IconType::Indexed4_32x32 |
+            IconType::Indexed4_16x16 |
+            IconType::Indexed8_32x32 |
+            IconType::Indexed8_16x16 => Encoding::RawIndexedWithMask,
+            IconType::RGB24_16x16 |
+            IconType::RGB24_32x32 |
+            IconType::RGB24_48x48 |
+            IconType::RGB24_128x128 => Encoding::RLE24,
+            IconType::RGBA32_16x16 |
+            IconType::RGBA32_16x16_2x |
+            IconType::RGBA32_32x32 |
+            IconType::RGBA32_32x32_2x |
+            IconType::RGBA32_64x64 |
+            IconType::RGBA32_128x128 |
+            IconType::RGBA32_128x128_2x |
+            IconType::RGBA32_256x256 |
+            IconType::RGBA32_256x256_2x |
+            IconType::RGBA32_512x512 |
+            IconType::RGBA32_512x512_2x => Encoding::JP2PNG,
+        }
+    }
+
+    /// Checks that `data` is a valid buffer of 32-bit RGBA pixel data for
+    /// this icon type (i.e. that its length is exactly
+    /// `4 * pixel_width() * pixel_width()` bytes).
+    pub fn validate_pixel_data(self, data: &[u8]) -> Result<(), PixelDataError> {
+        if data.len() % 4 != 0 {
+            return Err(PixelDataError::ByteCountNotDivisibleBy4(data.len()));
+        }
+        let width = self.pixel_width();
+        let expected_pixel_count = (width * width) as usize;
+        let actual_pixel_count = data.len() / 4;
+        if actual_pixel_count != expected_pixel_count {
+            return Err(PixelDataError::DimensionsVsPixelCount {
+                width: width,
+                height: width,
+                expected_pixel_count: expected_pixel_count,
+                actual_pixel_count: actual_pixel_count,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The bit depth of an icon type's (decoded) pixel data, analogous to the
+/// depth of a classic Windows `.bmp`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Depth {
+    /// 1 bit per pixel (plus a 1-bit mask).
+    One,
+    /// 4 bits per pixel (indexed, plus a 1-bit mask).
+    Four,
+    /// 8 bits per pixel (indexed, plus a 1-bit mask, or a raw alpha mask).
+    Eight,
+    /// 24 bits per pixel (RGB, no alpha).
+    TwentyFour,
+    /// 32 bits per pixel (RGBA).
+    ThirtyTwo,
+}
+
+/// The on-disk encoding used by an icon element's data.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Encoding {
+    /// Raw, uncompressed 8-bit alpha mask data.
+    Mask8,
+    /// Raw 1-bit-per-pixel bitmap data, immediately followed by a 1-bit
+    /// mask of equal size.
+    Raw1BitWithMask,
+    /// Raw indexed-color pixel data (4 or 8 bits per pixel), with the alpha
+    /// channel supplied by a separate mask element.
+    RawIndexedWithMask,
+    /// 24-bit RGB data, run-length encoded one channel at a time.
+    RLE24,
+    /// A full JPEG 2000 or PNG image.
+    JP2PNG,
+}
+
+/// An error indicating that a buffer of pixel data is not valid for a given
+/// icon type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PixelDataError {
+    /// The buffer's length is not a multiple of 4 (each RGBA pixel is 4
+    /// bytes).
+    ByteCountNotDivisibleBy4(usize),
+    /// The buffer's length (in pixels) does not match `width * height` for
+    /// the icon type's pixel dimensions.
+    DimensionsVsPixelCount {
+        /// The expected pixel width.
+        width: u32,
+        /// The expected pixel height.
+        height: u32,
+        /// The number of pixels the icon type's dimensions require.
+        expected_pixel_count: usize,
+        /// The number of pixels actually present in the buffer.
+        actual_pixel_count: usize,
+    },
+}
+
+impl fmt::Display for PixelDataError {
+    fn fmt(&self, out: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            PixelDataError::ByteCountNotDivisibleBy4(len) => {
+                write!(out, "pixel data length ({}) is not divisible by 4", len)
+            }
+            PixelDataError::DimensionsVsPixelCount { width, height, expected_pixel_count, actual_pixel_count } => {
+                write!(out,
+                       "pixel data has {} pixels, but {}x{} icon requires {} pixels",
+                       actual_pixel_count,
+                       width,
+                       height,
+                       expected_pixel_count)
+            }
+        }
+    }
 }
 
 /// A Macintosh OSType (also known as a ResType), used in ICNS files to
@@ -141,8 +503,23 @@ mod tests {
                           IconType::Mask8_16x16,
                           IconType::RGB24_32x32,
                           IconType::Mask8_32x32,
+                          IconType::Mono1_32x32,
+                          IconType::Mono1_16x16,
+                          IconType::Indexed4_32x32,
+                          IconType::Indexed4_16x16,
+                          IconType::Indexed8_32x32,
+                          IconType::Indexed8_16x16,
+                          IconType::RGB24_48x48,
+                          IconType::Mask8_48x48,
                           IconType::RGB24_128x128,
                           IconType::Mask8_128x128,
+                          IconType::RGBA32_16x16,
+                          IconType::RGBA32_16x16_2x,
+                          IconType::RGBA32_32x32,
+                          IconType::RGBA32_32x32_2x,
+                          IconType::RGBA32_64x64,
+                          IconType::RGBA32_128x128,
+                          IconType::RGBA32_128x128_2x,
                           IconType::RGBA32_256x256,
                           IconType::RGBA32_256x256_2x,
                           IconType::RGBA32_512x512,
@@ -153,4 +530,45 @@ mod tests {
             assert_eq!(Some(*icon_type), from);
         }
     }
+
+    #[test]
+    fn from_pixel_dimensions_prefers_non_retina_on_collision() {
+        assert_eq!(IconType::from_pixel_dimensions(32, 32), Some(IconType::RGBA32_32x32));
+        assert_eq!(IconType::from_pixel_dimensions(64, 64), Some(IconType::RGBA32_64x64));
+        assert_eq!(IconType::from_pixel_dimensions(512, 512), Some(IconType::RGBA32_512x512));
+        assert_eq!(IconType::from_pixel_dimensions(32, 16), None);
+    }
+
+    #[test]
+    fn best_for_prefers_retina_or_not() {
+        assert_eq!(IconType::best_for(32, 32, false), Some(IconType::RGBA32_32x32));
+        assert_eq!(IconType::best_for(32, 32, true), Some(IconType::RGBA32_16x16_2x));
+        assert_eq!(IconType::best_for(100, 100, false), Some(IconType::RGBA32_128x128));
+        assert_eq!(IconType::best_for(10000, 10000, false), Some(IconType::RGBA32_512x512_2x));
+        assert_eq!(IconType::best_for(10, 20, false), None);
+    }
+
+    #[test]
+    fn metadata_methods() {
+        assert!(IconType::Mask8_32x32.is_mask());
+        assert!(!IconType::RGB24_32x32.is_mask());
+        assert_eq!(IconType::RGBA32_32x32.channels(), 4);
+        assert_eq!(IconType::RGB24_32x32.channels(), 3);
+        assert_eq!(IconType::Indexed4_32x32.channels(), 1);
+        assert_eq!(IconType::Mono1_32x32.bits_per_pixel(), 1);
+        assert_eq!(IconType::Indexed8_32x32.bits_per_pixel(), 8);
+        assert_eq!(IconType::RGBA32_32x32.encoding(), Encoding::JP2PNG);
+        assert_eq!(IconType::RGB24_32x32.encoding(), Encoding::RLE24);
+        assert_eq!(IconType::Mask8_32x32.encoding(), Encoding::Mask8);
+        assert_eq!(IconType::Mono1_32x32.encoding(), Encoding::Raw1BitWithMask);
+        assert_eq!(IconType::Indexed8_32x32.encoding(), Encoding::RawIndexedWithMask);
+    }
+
+    #[test]
+    fn validate_pixel_data_rejects_bad_buffers() {
+        assert!(IconType::RGBA32_16x16.validate_pixel_data(&[0; 16 * 16 * 4]).is_ok());
+        assert_eq!(IconType::RGBA32_16x16.validate_pixel_data(&[0; 3]),
+                   Err(PixelDataError::ByteCountNotDivisibleBy4(3)));
+        assert!(IconType::RGBA32_16x16.validate_pixel_data(&[0; 8 * 8 * 4]).is_err());
+    }
 }
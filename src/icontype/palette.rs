@@ -0,0 +1,299 @@
+//! Fixed color tables used by the classic Macintosh indexed icon formats.
+//!
+//! The 4-bit and 8-bit icon elements (`icl4`/`ics4`/`icm4` and
+//! `icl8`/`ics8`/`icm8`) store a palette *index* per pixel rather than RGB
+//! values; the actual colors come from a palette that is fixed by the
+//! Macintosh Toolbox and is not stored in the file itself.
+//!
+//! The functions here are low-level pixel-decoding primitives, not a full
+//! element reader: they only combine bitmap/indexed data with a 1-bit mask
+//! of the same dimensions (such as the one the `ICN#`/`ics#` elements embed
+//! directly after their bitmap), and they return a raw RGBA buffer rather than an
+//! `Image`.  Combining these with a *separate* 8-bit mask element (e.g.
+//! `s8mk`/`l8mk`) or wrapping the result in an `Image` is left to whatever
+//! higher-level element/family reader eventually calls into this module.
+
+/// The standard 16-color Macintosh icon palette, used to expand 4-bit
+/// indexed icon data (`icl4`, `ics4`, `icm4`) into RGB.
+pub const MAC_4BIT_PALETTE: [(u8, u8, u8); 16] = [
+    (0xff, 0xff, 0xff),
+    (0xfc, 0xf3, 0x05),
+    (0xff, 0x64, 0x02),
+    (0xdd, 0x08, 0x06),
+    (0xf2, 0x08, 0x84),
+    (0x46, 0x00, 0xa5),
+    (0x00, 0x00, 0xd4),
+    (0x02, 0xab, 0xea),
+    (0x1f, 0xb7, 0x14),
+    (0x00, 0x64, 0x12),
+    (0x56, 0x2c, 0x05),
+    (0x90, 0x71, 0x3a),
+    (0xc0, 0xc0, 0xc0),
+    (0x80, 0x80, 0x80),
+    (0x40, 0x40, 0x40),
+    (0x00, 0x00, 0x00),
+];
+
+/// The standard 256-color Macintosh icon palette, used to expand 8-bit
+/// indexed icon data (`icl8`, `ics8`, `icm8`) into RGB.
+///
+/// The first 215 entries form the classic 6x6x6 "web safe" style color
+/// cube (with channel levels 0xFF, 0xCC, 0x99, 0x66, 0x33, 0x00, nested in
+/// R/G/B order) minus pure black, starting with pure white (index 0).  The
+/// next 40 entries are four 10-step ramps not present in the cube: pure
+/// reds, then pure greens, then pure blues, and then grays.  The last
+/// entry (index 255) is pure black.
+pub fn mac_8bit_palette() -> [(u8, u8, u8); 256] {
+    const LEVELS: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let mut palette = [(0u8, 0u8, 0u8); 256];
+    let mut index = 0;
+    for &r in &LEVELS {
+        for &g in &LEVELS {
+            for &b in &LEVELS {
+                if (r, g, b) != (0, 0, 0) {
+                    palette[index] = (r, g, b);
+                    index += 1;
+                }
+            }
+        }
+    }
+    for &v in &RAMP {
+        palette[index] = (v, 0, 0);
+        index += 1;
+    }
+    for &v in &RAMP {
+        palette[index] = (0, v, 0);
+        index += 1;
+    }
+    for &v in &RAMP {
+        palette[index] = (0, 0, v);
+        index += 1;
+    }
+    for &v in &RAMP {
+        palette[index] = (v, v, v);
+        index += 1;
+    }
+    // The final entry, palette[255], keeps its initial value: pure black.
+    palette
+}
+
+/// An error indicating that data passed to one of the `decode_*` functions
+/// in this module was too short or otherwise malformed to decode, as can
+/// happen with a truncated or corrupt legacy icon element.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    /// The bitmap/indexed pixel data is shorter than the dimensions
+    /// require.
+    PixelDataTooShort {
+        /// The number of bytes the given dimensions require.
+        expected: usize,
+        /// The number of bytes actually present.
+        actual: usize,
+    },
+    /// The 1-bit mask data is shorter than the dimensions require.
+    MaskDataTooShort {
+        /// The number of bytes the given dimensions require.
+        expected: usize,
+        /// The number of bytes actually present.
+        actual: usize,
+    },
+    /// An indexed pixel referenced a palette entry that doesn't exist.
+    PaletteIndexOutOfRange {
+        /// The out-of-range index that was encountered.
+        index: u8,
+        /// The number of entries in the palette.
+        palette_len: usize,
+    },
+    /// `decode_indexed_with_mask` was called with a `bits_per_index` other
+    /// than 4 or 8.
+    UnsupportedBitsPerIndex(u8),
+}
+
+/// Expands 1-bit-per-pixel bitmap data plus a 1-bit-per-pixel mask (as used
+/// by the `ICN#`/`ics#` element types) into RGBA pixel data.  Both `bitmap`
+/// and `mask` are tightly packed, row-major, most-significant-bit-first,
+/// with each row padded out to a whole number of bytes.  A set mask bit
+/// means the pixel is opaque; a set bitmap bit means the pixel is black.
+pub fn decode_1bit_with_mask(bitmap: &[u8],
+                              mask: &[u8],
+                              width: u32,
+                              height: u32)
+                              -> Result<Vec<u8>, DecodeError> {
+    let row_bytes = ((width + 7) / 8) as usize;
+    let needed = row_bytes * height as usize;
+    if bitmap.len() < needed {
+        return Err(DecodeError::PixelDataTooShort {
+            expected: needed,
+            actual: bitmap.len(),
+        });
+    }
+    if mask.len() < needed {
+        return Err(DecodeError::MaskDataTooShort {
+            expected: needed,
+            actual: mask.len(),
+        });
+    }
+    let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let byte_index = y * row_bytes + x / 8;
+            let bit = 7 - (x % 8);
+            let bitmap_bit = (bitmap[byte_index] >> bit) & 1;
+            let mask_bit = (mask[byte_index] >> bit) & 1;
+            let gray = if bitmap_bit == 1 { 0x00 } else { 0xff };
+            let alpha = if mask_bit == 1 { 0xff } else { 0x00 };
+            rgba.extend_from_slice(&[gray, gray, gray, alpha]);
+        }
+    }
+    Ok(rgba)
+}
+
+/// Expands indexed pixel data (as used by the `icl4`/`ics4`/`icm4` and
+/// `icl8`/`ics8`/`icm8` element types) plus a 1-bit-per-pixel mask into RGBA
+/// pixel data, by looking each pixel up in `palette`.
+///
+/// `bits_per_index` must be 4 (for `icl4`/`ics4`/`icm4`, where `indices`
+/// packs two pixels per byte, most-significant nibble first) or 8 (for
+/// `icl8`/`ics8`/`icm8`, one index per byte); any other value is rejected.
+/// As with the on-disk format, each row of `indices` is packed with no
+/// padding between rows.
+pub fn decode_indexed_with_mask(indices: &[u8],
+                                 mask: &[u8],
+                                 width: u32,
+                                 height: u32,
+                                 bits_per_index: u8,
+                                 palette: &[(u8, u8, u8)])
+                                 -> Result<Vec<u8>, DecodeError> {
+    let indices_row_bytes = match bits_per_index {
+        4 => ((width + 1) / 2) as usize,
+        8 => width as usize,
+        other => return Err(DecodeError::UnsupportedBitsPerIndex(other)),
+    };
+    let mask_row_bytes = ((width + 7) / 8) as usize;
+    let needed_indices = indices_row_bytes * height as usize;
+    let needed_mask = mask_row_bytes * height as usize;
+    if indices.len() < needed_indices {
+        return Err(DecodeError::PixelDataTooShort {
+            expected: needed_indices,
+            actual: indices.len(),
+        });
+    }
+    if mask.len() < needed_mask {
+        return Err(DecodeError::MaskDataTooShort {
+            expected: needed_mask,
+            actual: mask.len(),
+        });
+    }
+    let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let index = if bits_per_index == 4 {
+                let byte = indices[y * indices_row_bytes + x / 2];
+                if x % 2 == 0 { byte >> 4 } else { byte & 0x0f }
+            } else {
+                indices[y * indices_row_bytes + x]
+            };
+            let (r, g, b) = match palette.get(index as usize) {
+                Some(&rgb) => rgb,
+                None => {
+                    return Err(DecodeError::PaletteIndexOutOfRange {
+                        index: index,
+                        palette_len: palette.len(),
+                    })
+                }
+            };
+            let mask_byte = mask[y * mask_row_bytes + x / 8];
+            let mask_bit = 7 - (x % 8);
+            let alpha = if (mask_byte >> mask_bit) & 1 == 1 { 0xff } else { 0x00 };
+            rgba.extend_from_slice(&[r, g, b, alpha]);
+        }
+    }
+    Ok(rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_sizes() {
+        assert_eq!(MAC_4BIT_PALETTE.len(), 16);
+        assert_eq!(mac_8bit_palette().len(), 256);
+    }
+
+    #[test]
+    fn palette_endpoints() {
+        assert_eq!(MAC_4BIT_PALETTE[0], (0xff, 0xff, 0xff));
+        assert_eq!(MAC_4BIT_PALETTE[15], (0x00, 0x00, 0x00));
+        let palette = mac_8bit_palette();
+        assert_eq!(palette[0], (0xff, 0xff, 0xff));
+        assert_eq!(palette[214], (0x00, 0x00, 0x33));
+        assert_eq!(palette[215], (0xee, 0x00, 0x00));
+        assert_eq!(palette[225], (0x00, 0xee, 0x00));
+        assert_eq!(palette[235], (0x00, 0x00, 0xee));
+        assert_eq!(palette[255], (0x00, 0x00, 0x00));
+    }
+
+    #[test]
+    fn decode_1bit_black_and_white_rows() {
+        // 8x2 bitmap: row 0 all black, row 1 all white; mask fully opaque.
+        let bitmap = [0xff, 0x00];
+        let mask = [0xff, 0xff];
+        let rgba = decode_1bit_with_mask(&bitmap, &mask, 8, 2).unwrap();
+        assert_eq!(rgba.len(), 8 * 2 * 4);
+        assert_eq!(&rgba[0..4], &[0x00, 0x00, 0x00, 0xff]);
+        assert_eq!(&rgba[8 * 4..8 * 4 + 4], &[0xff, 0xff, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn decode_1bit_rejects_truncated_data() {
+        let bitmap = [0xff];
+        let mask = [0xff, 0xff];
+        assert_eq!(decode_1bit_with_mask(&bitmap, &mask, 8, 2),
+                   Err(DecodeError::PixelDataTooShort {
+                       expected: 2,
+                       actual: 1,
+                   }));
+    }
+
+    #[test]
+    fn decode_indexed_8bit_rejects_bad_data() {
+        let indices = [0u8; 4];
+        let mask = [0xffu8, 0xff];
+        assert_eq!(decode_indexed_with_mask(&indices, &mask, 2, 2, 8, &MAC_4BIT_PALETTE).is_ok(),
+                   true);
+        let short_indices = [0u8; 3];
+        assert_eq!(decode_indexed_with_mask(&short_indices, &mask, 2, 2, 8, &MAC_4BIT_PALETTE),
+                   Err(DecodeError::PixelDataTooShort {
+                       expected: 4,
+                       actual: 3,
+                   }));
+        let bad_palette_indices = [20u8, 0, 0, 0];
+        assert_eq!(decode_indexed_with_mask(&bad_palette_indices, &mask, 2, 2, 8, &MAC_4BIT_PALETTE),
+                   Err(DecodeError::PaletteIndexOutOfRange {
+                       index: 20,
+                       palette_len: 16,
+                   }));
+    }
+
+    #[test]
+    fn decode_indexed_4bit_unpacks_nibbles() {
+        // 4x2 icl4-style icon, 2 bytes per row (2 pixels per byte).  Row 0:
+        // indices 1, 2, 3, 4; row 1: indices 5, 6, 7, 8.
+        let indices = [0x12, 0x34, 0x56, 0x78];
+        let mask = [0xff, 0xff];
+        let rgba = decode_indexed_with_mask(&indices, &mask, 4, 2, 4, &MAC_4BIT_PALETTE).unwrap();
+        assert_eq!(rgba.len(), 4 * 2 * 4);
+        assert_eq!(&rgba[0..4], &[MAC_4BIT_PALETTE[1].0, MAC_4BIT_PALETTE[1].1, MAC_4BIT_PALETTE[1].2, 0xff]);
+        assert_eq!(&rgba[4..8], &[MAC_4BIT_PALETTE[2].0, MAC_4BIT_PALETTE[2].1, MAC_4BIT_PALETTE[2].2, 0xff]);
+        assert_eq!(&rgba[12..16], &[MAC_4BIT_PALETTE[4].0, MAC_4BIT_PALETTE[4].1, MAC_4BIT_PALETTE[4].2, 0xff]);
+        assert_eq!(&rgba[16..20], &[MAC_4BIT_PALETTE[5].0, MAC_4BIT_PALETTE[5].1, MAC_4BIT_PALETTE[5].2, 0xff]);
+    }
+
+    #[test]
+    fn decode_indexed_rejects_unsupported_bits_per_index() {
+        assert_eq!(decode_indexed_with_mask(&[0; 4], &[0xff, 0xff], 2, 2, 2, &MAC_4BIT_PALETTE),
+                   Err(DecodeError::UnsupportedBitsPerIndex(2)));
+    }
+}
@@ -0,0 +1,201 @@
+//! Support for the `TOC ` (table of contents) element that modern `.icns`
+//! files place first, listing the OSType and length of every subsequent
+//! element so that readers can seek directly to the element they want
+//! without scanning the whole file.
+//!
+//! This module only parses/serializes the `TOC ` element's own bytes; it
+//! is not yet wired into a reader or writer.  There is no `IconFamily` in
+//! this crate yet, so nothing here actually skips decoding un-requested
+//! elements, seeks using a parsed table, or prepends a `TOC ` element when
+//! writing a family to disk — that integration is still to be done once
+//! such a reader/writer exists.
+
+use super::OSType;
+
+/// The OSType of the table-of-contents element itself.
+pub const TOC_OSTYPE: OSType = OSType(*b"TOC ");
+
+/// The size, in bytes, of an icon element's header (a 4-byte OSType
+/// followed by a 4-byte big-endian length, which includes the header
+/// itself).
+const ELEMENT_HEADER_SIZE: u32 = 8;
+
+/// One record within a `TOC ` element, giving the OSType and total length
+/// (including its own 8-byte header) of one of the other elements in the
+/// file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TocEntry {
+    /// The OSType of the element this entry describes.
+    pub ostype: OSType,
+    /// The total length, in bytes, of the element this entry describes
+    /// (including its 8-byte header).
+    pub length: u32,
+}
+
+/// A parsed `TOC ` element, used to locate other elements within an
+/// `.icns` file without having to scan past their data.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TableOfContents {
+    entries: Vec<TocEntry>,
+}
+
+/// An error indicating that a `TOC ` element's data could not be parsed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TocError {
+    /// The element data length is not a multiple of 8 bytes (one record
+    /// per listed element).
+    RecordDataMisaligned(usize),
+    /// A record claims a length shorter than the 8-byte element header
+    /// size, which is impossible for a real element.
+    ElementTooShort {
+        /// The OSType of the offending record.
+        ostype: OSType,
+        /// The (too-short) length the record claims.
+        length: u32,
+    },
+    /// The lengths listed in the table add up to more bytes than are
+    /// actually present in the file.
+    TotalLengthExceedsFile {
+        /// The total length implied by the table (including the `TOC `
+        /// element itself).
+        total_length: u64,
+        /// The actual length of the file.
+        file_length: u64,
+    },
+}
+
+impl TableOfContents {
+    /// Parses the body of a `TOC ` element (i.e. the element's data, not
+    /// including its own 8-byte header) into a table of contents.
+    pub fn parse(data: &[u8]) -> Result<TableOfContents, TocError> {
+        if data.len() % 8 != 0 {
+            return Err(TocError::RecordDataMisaligned(data.len()));
+        }
+        let mut entries = Vec::with_capacity(data.len() / 8);
+        for record in data.chunks(8) {
+            let ostype = OSType([record[0], record[1], record[2], record[3]]);
+            let length = u32::from_be_bytes([record[4], record[5], record[6], record[7]]);
+            if length < ELEMENT_HEADER_SIZE {
+                return Err(TocError::ElementTooShort {
+                    ostype: ostype,
+                    length: length,
+                });
+            }
+            entries.push(TocEntry {
+                ostype: ostype,
+                length: length,
+            });
+        }
+        Ok(TableOfContents { entries: entries })
+    }
+
+    /// Checks that the lengths listed in this table, plus the table's own
+    /// element header and body, do not add up to more than `file_length`
+    /// bytes.
+    pub fn validate_total_length(&self, file_length: u64) -> Result<(), TocError> {
+        let toc_element_length = ELEMENT_HEADER_SIZE as u64 + (self.entries.len() as u64 * 8);
+        let total_length: u64 = toc_element_length +
+                                 self.entries.iter().map(|entry| entry.length as u64).sum::<u64>();
+        if total_length > file_length {
+            return Err(TocError::TotalLengthExceedsFile {
+                total_length: total_length,
+                file_length: file_length,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the listed entries, in file order.
+    pub fn entries(&self) -> &[TocEntry] {
+        &self.entries
+    }
+
+    /// Looks up the entry for the given OSType, if the table lists one.
+    pub fn find(&self, ostype: OSType) -> Option<&TocEntry> {
+        self.entries.iter().find(|entry| entry.ostype == ostype)
+    }
+
+    /// Builds a table of contents describing the given elements (each given
+    /// as an OSType paired with that element's total length, including its
+    /// own 8-byte header), in the order they will appear in the file.
+    pub fn from_elements(elements: &[(OSType, u32)]) -> TableOfContents {
+        let entries = elements.iter()
+            .map(|&(ostype, length)| {
+                TocEntry {
+                    ostype: ostype,
+                    length: length,
+                }
+            })
+            .collect();
+        TableOfContents { entries: entries }
+    }
+
+    /// Returns the total length, in bytes, of this `TOC ` element itself
+    /// (its 8-byte header plus one 8-byte record per entry).
+    pub fn element_length(&self) -> u32 {
+        ELEMENT_HEADER_SIZE + (self.entries.len() as u32 * 8)
+    }
+
+    /// Serializes this table of contents as a complete `TOC ` element
+    /// (including its own 8-byte header), suitable for writing as the
+    /// first element of an `.icns` file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.element_length() as usize);
+        let OSType(raw_ostype) = TOC_OSTYPE;
+        bytes.extend_from_slice(&raw_ostype);
+        bytes.extend_from_slice(&self.element_length().to_be_bytes());
+        for entry in &self.entries {
+            let OSType(raw_entry_ostype) = entry.ostype;
+            bytes.extend_from_slice(&raw_entry_ostype);
+            bytes.extend_from_slice(&entry.length.to_be_bytes());
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let elements = [(OSType(*b"is32"), 100), (OSType(*b"s8mk"), 50)];
+        let toc = TableOfContents::from_elements(&elements);
+        let bytes = toc.to_bytes();
+        let parsed = TableOfContents::parse(&bytes[8..]).unwrap();
+        assert_eq!(parsed.entries(), toc.entries());
+        assert_eq!(parsed.find(OSType(*b"is32")), Some(&TocEntry {
+            ostype: OSType(*b"is32"),
+            length: 100,
+        }));
+    }
+
+    #[test]
+    fn rejects_misaligned_data() {
+        assert_eq!(TableOfContents::parse(&[0; 5]), Err(TocError::RecordDataMisaligned(5)));
+    }
+
+    #[test]
+    fn rejects_too_short_element() {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"is32");
+        data.extend_from_slice(&3u32.to_be_bytes());
+        assert_eq!(TableOfContents::parse(&data),
+                   Err(TocError::ElementTooShort {
+                       ostype: OSType(*b"is32"),
+                       length: 3,
+                   }));
+    }
+
+    #[test]
+    fn rejects_table_bigger_than_file() {
+        let elements = [(OSType(*b"is32"), 1000)];
+        let toc = TableOfContents::from_elements(&elements);
+        assert_eq!(toc.validate_total_length(100),
+                   Err(TocError::TotalLengthExceedsFile {
+                       total_length: 1016,
+                       file_length: 100,
+                   }));
+        assert!(toc.validate_total_length(2000).is_ok());
+    }
+}